@@ -0,0 +1,60 @@
+// Copyright 2018 OpenST Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::Block;
+
+/// A `ChainUpdate` is emitted by a reorg-aware block stream instead of a plain `Block`.
+///
+/// Consumers must be prepared to undo any side effects they applied for a block that is later
+/// reported as `Reverted`, as that block turned out to live on a side chain that got discarded by
+/// a chain reorganization.
+///
+/// Generic over the event payload type `E` so that this low-level, connector-agnostic type does
+/// not have to depend on any one blockchain connector's concrete event set. Mosaic's `ethereum`
+/// connector instantiates this as `ChainUpdate<ethereum::MosaicEvent>`.
+///
+/// Alongside the block, an `Applied` update carries the typed events `attach_events` decoded out
+/// of it. Blocks that were fetched while walking back through a reorg to find a common ancestor,
+/// rather than observed directly, carry no events: events are only ever decoded for directly
+/// observed blocks. `Reverted` updates carry no events either, as the reorg history keeps only
+/// the blocks and not the events decoded from them.
+#[derive(Debug, Clone)]
+pub enum ChainUpdate<E> {
+    /// A block that is (currently) part of the canonical chain, with the events decoded from it.
+    Applied(Block, Vec<E>),
+    /// A block that was previously applied, but got discarded by a chain reorganization and must
+    /// be undone by consumers. The events that were decoded from it when it was applied are not
+    /// retained, so the event list is empty.
+    Reverted(Block, Vec<E>),
+}
+
+impl<E> ChainUpdate<E> {
+    /// Returns a reference to the block that this update carries, regardless of whether it was
+    /// applied or reverted.
+    pub fn block(&self) -> &Block {
+        match self {
+            ChainUpdate::Applied(block, _) => block,
+            ChainUpdate::Reverted(block, _) => block,
+        }
+    }
+
+    /// Returns the events that were decoded from this update's block, regardless of whether it
+    /// was applied or reverted.
+    pub fn events(&self) -> &[E] {
+        match self {
+            ChainUpdate::Applied(_, events) => events,
+            ChainUpdate::Reverted(_, events) => events,
+        }
+    }
+}
@@ -18,6 +18,7 @@ pub mod address;
 pub mod basic_types;
 pub mod block;
 pub mod bytes;
+pub mod chain_update;
 pub mod error;
 pub mod signature;
 
@@ -25,5 +26,6 @@ pub use self::address::*;
 pub use self::basic_types::*;
 pub use self::block::*;
 pub use self::bytes::*;
+pub use self::chain_update::*;
 pub use self::error::*;
 pub use self::signature::*;
@@ -0,0 +1,158 @@
+// Copyright 2018 OpenST Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed, ABI-derived bindings for the events that the Mosaic gateway and co-gateway contracts
+//! emit.
+//!
+//! Each binding implements `EthEvent`, which gives `stream_blocks` two things that the old,
+//! untyped `log_into_event` path could not: a `signature()` to build a topic filter from (so the
+//! node only ever sends us logs we actually care about), and a `decode_log` that turns a raw log
+//! directly into a strongly-typed struct with compile-time-checked fields.
+
+use error::{Error, ErrorKind};
+use ethabi::RawLog;
+use tiny_keccak::Keccak;
+use web3::types::{Address, H256, U256};
+
+/// A strongly-typed Ethereum event, generated or hand-written from a contract's ABI.
+pub trait EthEvent: Sized {
+    /// The event's name, as declared in the contract ABI, e.g. `"StakeIntentDeclared"`.
+    fn name() -> &'static str;
+
+    /// The event's canonical ABI signature, e.g. `"StateRootAvailable(uint256,bytes32)"`.
+    fn abi_signature() -> &'static str;
+
+    /// The keccak-256 hash of `abi_signature()`. This is the value the EVM places in `topic0` of
+    /// every log that this event produces, so it is what we filter logs by.
+    fn signature() -> H256 {
+        let mut keccak = Keccak::new_keccak256();
+        let mut hash = [0u8; 32];
+        keccak.update(Self::abi_signature().as_bytes());
+        keccak.finalize(&mut hash);
+
+        H256::from(hash)
+    }
+
+    /// Decodes a raw log into this event. Fails if the log's topics or data do not match what is
+    /// expected for this event.
+    fn decode_log(log: &RawLog) -> Result<Self, Error>;
+}
+
+/// A strongly-typed event decoded out of a block's logs by `attach_events`.
+///
+/// Combines every `EthEvent` binding into one type so that a block can carry a single
+/// `Vec<MosaicEvent>` of everything it matched, instead of one `Vec` per event type.
+#[derive(Debug, Clone)]
+pub enum MosaicEvent {
+    /// See `StakeIntentDeclared`.
+    StakeIntentDeclared(StakeIntentDeclared),
+    /// See `StateRootAvailable`.
+    StateRootAvailable(StateRootAvailable),
+}
+
+/// Emitted by the gateway contract on origin when a stake intent is declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StakeIntentDeclared {
+    pub message_hash: H256,
+    pub staker: Address,
+    pub staker_nonce: U256,
+    pub amount: U256,
+}
+
+impl EthEvent for StakeIntentDeclared {
+    fn name() -> &'static str {
+        "StakeIntentDeclared"
+    }
+
+    fn abi_signature() -> &'static str {
+        "StakeIntentDeclared(bytes32,address,uint256,uint256)"
+    }
+
+    fn decode_log(log: &RawLog) -> Result<Self, Error> {
+        // `topic0` is the event signature itself; the indexed arguments follow it.
+        let message_hash = *log.topics.get(1).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidBlock,
+                "StakeIntentDeclared log is missing the message hash topic".to_string(),
+            )
+        })?;
+
+        let staker = log
+            .topics
+            .get(2)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidBlock,
+                    "StakeIntentDeclared log is missing the staker topic".to_string(),
+                )
+            }).map(Address::from)?;
+
+        if log.data.len() < 64 {
+            return Err(Error::new(
+                ErrorKind::InvalidBlock,
+                "StakeIntentDeclared log data is shorter than two words".to_string(),
+            ));
+        }
+
+        let staker_nonce = U256::from_big_endian(&log.data[0..32]);
+        let amount = U256::from_big_endian(&log.data[32..64]);
+
+        Ok(StakeIntentDeclared {
+            message_hash,
+            staker,
+            staker_nonce,
+            amount,
+        })
+    }
+}
+
+/// Emitted by the co-gateway contract on auxiliary when it learns of a new state root from
+/// origin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateRootAvailable {
+    pub block_height: U256,
+    pub state_root: H256,
+}
+
+impl EthEvent for StateRootAvailable {
+    fn name() -> &'static str {
+        "StateRootAvailable"
+    }
+
+    fn abi_signature() -> &'static str {
+        "StateRootAvailable(uint256,bytes32)"
+    }
+
+    fn decode_log(log: &RawLog) -> Result<Self, Error> {
+        let block_height = *log.topics.get(1).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidBlock,
+                "StateRootAvailable log is missing the block height topic".to_string(),
+            )
+        })?;
+        let block_height = U256::from_big_endian(&block_height.0);
+
+        let state_root = *log.topics.get(2).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidBlock,
+                "StateRootAvailable log is missing the state root topic".to_string(),
+            )
+        })?;
+
+        Ok(StateRootAvailable {
+            block_height,
+            state_root,
+        })
+    }
+}
@@ -0,0 +1,372 @@
+// Copyright 2018 OpenST Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This module abstracts over the concrete `Ethereum` connection with a `Provider`
+//! trait, so that the rest of mosaic can be driven against a mock provider in tests, and so that
+//! cross-cutting concerns (nonce management, gas pricing) can be layered on independently.
+//!
+//! Layers are implemented the same way web3 middleware stacks typically are: each layer wraps an
+//! inner `Box<dyn Provider>` and forwards every call it does not itself care about, overriding
+//! only the methods that implement its concern.
+
+use super::events::MosaicEvent;
+use super::types::{ChainUpdate, Signature};
+use error::Error;
+use event::EventHandler;
+use futures::future;
+use futures::prelude::*;
+use std::cell::RefCell;
+use std::sync::Arc;
+use web3::contract::Contract;
+use web3::transports::Http;
+use web3::types::{Address, Bytes, U256};
+
+use super::super::reactor::Reactor;
+
+/// An Ethereum connection, abstracted over its transport and over cross-cutting concerns such as
+/// nonce management and gas pricing.
+///
+/// Implementations are expected to be cheap to notify and to forward unhandled calls to an inner
+/// provider where applicable, mirroring a stackable middleware architecture.
+pub trait Provider {
+    /// Streams `ChainUpdate`s observed on the underlying chain. See `Ethereum::stream_blocks` for
+    /// the concrete behavior over HTTP and WebSocket endpoints.
+    fn stream_blocks(
+        &self,
+        event_handler: Arc<EventHandler>,
+    ) -> Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>>;
+
+    /// Replays blocks from `from_block` (inclusive) up to the current head as
+    /// `ChainUpdate::Applied`. Used to catch up on blocks missed while reconnecting a dropped
+    /// `stream_blocks`. See `Ethereum::backfill` for the concrete behavior.
+    fn backfill(
+        &self,
+        from_block: u64,
+        event_handler: Arc<EventHandler>,
+    ) -> Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>>;
+
+    /// Retrieves the accounts known to this provider.
+    fn get_accounts(&self) -> Box<dyn Future<Item = Vec<Address>, Error = Error>>;
+
+    /// Signs the given data with the validator's key.
+    fn sign(&self, data: Bytes) -> Box<dyn Future<Item = Signature, Error = Error>>;
+
+    /// Returns the gas price to use for the next transaction.
+    fn gas_price(&self) -> Box<dyn Future<Item = U256, Error = Error>>;
+
+    /// Returns the next nonce to use for a transaction sent from `address`.
+    fn next_nonce(&self, address: Address) -> Box<dyn Future<Item = U256, Error = Error>>;
+
+    /// Creates a contract instance at `contract_address` from its ABI.
+    fn contract_instance(
+        &self,
+        contract_address: Address,
+        abi: &[u8],
+    ) -> Result<Contract<Http>, Error>;
+
+    /// Registers a block reactor that is notified of every chain update, applied or reverted.
+    fn register_reactor(&mut self, reactor: Reactor);
+
+    /// Notifies all registered reactors of a chain update.
+    fn notify_reactors(&self, chain_update: &ChainUpdate<MosaicEvent>);
+}
+
+/// A pluggable source of gas prices, used by `GasOracle` instead of asking the node.
+pub trait GasPriceSource {
+    /// Returns the gas price that should be used for the next transaction.
+    fn gas_price(&self) -> Box<dyn Future<Item = U256, Error = Error>>;
+}
+
+/// A `Provider` middleware that tracks and increments the validator's nonce locally, instead of
+/// asking the inner provider's node for the transaction count on every call. This allows signing
+/// multiple transactions before any of them has been mined.
+pub struct NonceManager {
+    inner: Box<dyn Provider>,
+    next_nonce: RefCell<U256>,
+}
+
+impl NonceManager {
+    /// Wraps `inner`, tracking nonces locally starting from `starting_nonce`. Callers typically
+    /// obtain `starting_nonce` by awaiting `inner.next_nonce(validator)` once, before handing the
+    /// inner provider off to the `NonceManager`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider to wrap.
+    /// * `starting_nonce` - The nonce to hand out on the first call to `next_nonce`.
+    pub fn new(inner: Box<dyn Provider>, starting_nonce: U256) -> Self {
+        NonceManager {
+            inner,
+            next_nonce: RefCell::new(starting_nonce),
+        }
+    }
+}
+
+impl Provider for NonceManager {
+    fn stream_blocks(
+        &self,
+        event_handler: Arc<EventHandler>,
+    ) -> Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>> {
+        self.inner.stream_blocks(event_handler)
+    }
+
+    fn backfill(
+        &self,
+        from_block: u64,
+        event_handler: Arc<EventHandler>,
+    ) -> Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>> {
+        self.inner.backfill(from_block, event_handler)
+    }
+
+    fn get_accounts(&self) -> Box<dyn Future<Item = Vec<Address>, Error = Error>> {
+        self.inner.get_accounts()
+    }
+
+    fn sign(&self, data: Bytes) -> Box<dyn Future<Item = Signature, Error = Error>> {
+        self.inner.sign(data)
+    }
+
+    fn gas_price(&self) -> Box<dyn Future<Item = U256, Error = Error>> {
+        self.inner.gas_price()
+    }
+
+    fn next_nonce(&self, _address: Address) -> Box<dyn Future<Item = U256, Error = Error>> {
+        let mut next_nonce = self.next_nonce.borrow_mut();
+        let nonce = *next_nonce;
+        *next_nonce = nonce + U256::one();
+
+        Box::new(future::ok(nonce))
+    }
+
+    fn contract_instance(
+        &self,
+        contract_address: Address,
+        abi: &[u8],
+    ) -> Result<Contract<Http>, Error> {
+        self.inner.contract_instance(contract_address, abi)
+    }
+
+    fn register_reactor(&mut self, reactor: Reactor) {
+        self.inner.register_reactor(reactor)
+    }
+
+    fn notify_reactors(&self, chain_update: &ChainUpdate<MosaicEvent>) {
+        self.inner.notify_reactors(chain_update)
+    }
+}
+
+/// A `Provider` middleware that fills the gas price from a pluggable `GasPriceSource` instead of
+/// asking the inner provider's node for it.
+pub struct GasOracle {
+    inner: Box<dyn Provider>,
+    source: Box<dyn GasPriceSource>,
+}
+
+impl GasOracle {
+    /// Wraps `inner`, answering `gas_price` from `source` instead of from `inner`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider to wrap.
+    /// * `source` - The pluggable gas price source to use instead of the inner provider's node.
+    pub fn new(inner: Box<dyn Provider>, source: Box<dyn GasPriceSource>) -> Self {
+        GasOracle { inner, source }
+    }
+}
+
+impl Provider for GasOracle {
+    fn stream_blocks(
+        &self,
+        event_handler: Arc<EventHandler>,
+    ) -> Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>> {
+        self.inner.stream_blocks(event_handler)
+    }
+
+    fn backfill(
+        &self,
+        from_block: u64,
+        event_handler: Arc<EventHandler>,
+    ) -> Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>> {
+        self.inner.backfill(from_block, event_handler)
+    }
+
+    fn get_accounts(&self) -> Box<dyn Future<Item = Vec<Address>, Error = Error>> {
+        self.inner.get_accounts()
+    }
+
+    fn sign(&self, data: Bytes) -> Box<dyn Future<Item = Signature, Error = Error>> {
+        self.inner.sign(data)
+    }
+
+    fn gas_price(&self) -> Box<dyn Future<Item = U256, Error = Error>> {
+        self.source.gas_price()
+    }
+
+    fn next_nonce(&self, address: Address) -> Box<dyn Future<Item = U256, Error = Error>> {
+        self.inner.next_nonce(address)
+    }
+
+    fn contract_instance(
+        &self,
+        contract_address: Address,
+        abi: &[u8],
+    ) -> Result<Contract<Http>, Error> {
+        self.inner.contract_instance(contract_address, abi)
+    }
+
+    fn register_reactor(&mut self, reactor: Reactor) {
+        self.inner.register_reactor(reactor)
+    }
+
+    fn notify_reactors(&self, chain_update: &ChainUpdate<MosaicEvent>) {
+        self.inner.notify_reactors(chain_update)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    /// A `Provider` that answers every call with a fixed, distinctive value and never talks to a
+    /// node, so `NonceManager` and `GasOracle` can be tested as plain middleware: whatever they
+    /// don't override should come straight back out of this stub.
+    struct StubProvider {
+        gas_price: U256,
+        next_nonce: U256,
+    }
+
+    impl Provider for StubProvider {
+        fn stream_blocks(
+            &self,
+            _event_handler: Arc<EventHandler>,
+        ) -> Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>> {
+            Box::new(stream::empty())
+        }
+
+        fn backfill(
+            &self,
+            _from_block: u64,
+            _event_handler: Arc<EventHandler>,
+        ) -> Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>> {
+            Box::new(stream::empty())
+        }
+
+        fn get_accounts(&self) -> Box<dyn Future<Item = Vec<Address>, Error = Error>> {
+            Box::new(future::ok(Vec::new()))
+        }
+
+        fn sign(&self, _data: Bytes) -> Box<dyn Future<Item = Signature, Error = Error>> {
+            unimplemented!("not exercised by these tests")
+        }
+
+        fn gas_price(&self) -> Box<dyn Future<Item = U256, Error = Error>> {
+            Box::new(future::ok(self.gas_price))
+        }
+
+        fn next_nonce(&self, _address: Address) -> Box<dyn Future<Item = U256, Error = Error>> {
+            Box::new(future::ok(self.next_nonce))
+        }
+
+        fn contract_instance(
+            &self,
+            _contract_address: Address,
+            _abi: &[u8],
+        ) -> Result<Contract<Http>, Error> {
+            unimplemented!("not exercised by these tests")
+        }
+
+        fn register_reactor(&mut self, _reactor: Reactor) {
+            unimplemented!("not exercised by these tests")
+        }
+
+        fn notify_reactors(&self, _chain_update: &ChainUpdate<MosaicEvent>) {}
+    }
+
+    #[test]
+    fn nonce_manager_hands_out_incrementing_nonces_without_asking_the_inner_provider() {
+        let inner = StubProvider {
+            gas_price: U256::from(1),
+            // Deliberately different from `starting_nonce` below, so a test failure that leaks
+            // the inner provider's nonce through is obvious.
+            next_nonce: U256::from(999),
+        };
+        let nonce_manager = NonceManager::new(Box::new(inner), U256::from(5));
+
+        let first = nonce_manager
+            .next_nonce(Address::zero())
+            .wait()
+            .expect("cannot fail");
+        let second = nonce_manager
+            .next_nonce(Address::zero())
+            .wait()
+            .expect("cannot fail");
+
+        assert_eq!(first, U256::from(5));
+        assert_eq!(second, U256::from(6));
+    }
+
+    #[test]
+    fn nonce_manager_forwards_gas_price_to_the_inner_provider() {
+        let inner = StubProvider {
+            gas_price: U256::from(42),
+            next_nonce: U256::zero(),
+        };
+        let nonce_manager = NonceManager::new(Box::new(inner), U256::zero());
+
+        let gas_price = nonce_manager.gas_price().wait().expect("cannot fail");
+
+        assert_eq!(gas_price, U256::from(42));
+    }
+
+    /// A `GasPriceSource` that always returns the same, pre-configured price.
+    struct FixedGasPriceSource(U256);
+
+    impl GasPriceSource for FixedGasPriceSource {
+        fn gas_price(&self) -> Box<dyn Future<Item = U256, Error = Error>> {
+            Box::new(future::ok(self.0))
+        }
+    }
+
+    #[test]
+    fn gas_oracle_answers_gas_price_from_its_source_instead_of_the_inner_provider() {
+        let inner = StubProvider {
+            gas_price: U256::from(1),
+            next_nonce: U256::zero(),
+        };
+        let gas_oracle =
+            GasOracle::new(Box::new(inner), Box::new(FixedGasPriceSource(U256::from(7))));
+
+        let gas_price = gas_oracle.gas_price().wait().expect("cannot fail");
+
+        assert_eq!(gas_price, U256::from(7));
+    }
+
+    #[test]
+    fn gas_oracle_forwards_next_nonce_to_the_inner_provider() {
+        let inner = StubProvider {
+            gas_price: U256::from(1),
+            next_nonce: U256::from(13),
+        };
+        let gas_oracle =
+            GasOracle::new(Box::new(inner), Box::new(FixedGasPriceSource(U256::from(7))));
+
+        let next_nonce = gas_oracle
+            .next_nonce(Address::zero())
+            .wait()
+            .expect("cannot fail");
+
+        assert_eq!(next_nonce, U256::from(13));
+    }
+}
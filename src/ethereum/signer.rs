@@ -0,0 +1,110 @@
+// Copyright 2018 OpenST Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signing backends for the validator account.
+//!
+//! `Signer` abstracts signing behind a trait so that mosaic is not hard-wired to asking the
+//! connected node to sign on the validator's behalf. `SignerBackend` only offers `NodeUnlock`
+//! today: a local-keystore and a hardware-wallet backend were attempted but neither could
+//! actually sign (decrypting a keystore file and talking to a hardware wallet over USB HID are
+//! both still unimplemented), so they were removed rather than shipped as selectable options that
+//! silently fail. Add them back here, as additional `SignerBackend` variants alongside a
+//! `Signer` impl, once they can really sign.
+
+use super::types::Signature;
+use error::{Error, ErrorKind};
+use futures::prelude::*;
+use rpassword;
+use web3::transports::Http;
+use web3::types::{Bytes, H160};
+use web3::{Transport, Web3};
+
+/// Signs data on behalf of the validator account.
+pub trait Signer {
+    /// Signs `data` and returns the resulting signature.
+    fn sign(&self, data: Bytes) -> Box<dyn Future<Item = Signature, Error = Error>>;
+}
+
+/// Selects which `Signer` backend `Ethereum::new` should construct.
+pub enum SignerBackend {
+    /// Signs by asking the connected node to unlock the validator account with a password read
+    /// from `stdin`, as mosaic has always done. The private key lives on the node.
+    NodeUnlock,
+}
+
+/// Signs by unlocking the validator account on the connected node and asking the node to sign.
+///
+/// Generic over the node connection's transport, so that it works the same whether `Ethereum` is
+/// connected over HTTP or over a WebSocket subscription.
+pub struct NodeUnlockSigner<T: Transport = Http> {
+    web3: Web3<T>,
+    validator: H160,
+    /// The password to unlock the validator account on the node.
+    password: String,
+}
+
+impl<T: Transport> NodeUnlockSigner<T> {
+    /// Creates a new `NodeUnlockSigner`, reading the password to unlock `validator` from `stdin`.
+    ///
+    /// # Arguments
+    ///
+    /// * `web3` - The connection to the node that holds the validator's private key.
+    /// * `validator` - The address of the validator account to unlock.
+    pub fn new(web3: Web3<T>, validator: H160) -> Self {
+        let password = rpassword::prompt_password_stdout(&format!(
+            "Please enter the password for account {:x}: ",
+            &validator,
+        )).unwrap();
+
+        NodeUnlockSigner {
+            web3,
+            validator,
+            password,
+        }
+    }
+
+    /// Unlocks the validator account of this signer using the stored password.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - If given, will unlock for the duration in seconds. Otherwise for a single
+    /// transaction.
+    fn unlock_account(&self, duration: Option<u16>) -> impl Future<Item = bool, Error = Error> {
+        self.web3
+            .personal()
+            .unlock_account(self.validator, &self.password, duration)
+            .map_err(|error| {
+                Error::new(
+                    ErrorKind::NodeError,
+                    format!("Was not able to unlock account: {}", error),
+                )
+            })
+    }
+}
+
+impl<T: Transport + 'static> Signer for NodeUnlockSigner<T> {
+    fn sign(&self, data: Bytes) -> Box<dyn Future<Item = Signature, Error = Error>> {
+        let web3 = self.web3.clone();
+        let validator = self.validator;
+
+        Box::new(self.unlock_account(None).and_then(move |_| {
+            web3.eth().sign(validator, data).map_err(|error| {
+                Error::new(
+                    ErrorKind::NodeError,
+                    format!("Was not able to sign data: {}", error),
+                )
+            })
+        }))
+    }
+}
@@ -17,10 +17,15 @@
 //! actions for each new block that it observes on the connected chains.
 
 use super::error::Error;
-use super::ethereum::{Block, Ethereum};
+use super::ethereum::{ChainUpdate, MosaicEvent, Provider};
 use super::event;
+use super::event::EventHandler;
 use super::Config;
+use futures::future::{self, Loop};
 use futures::prelude::*;
+use futures::stream;
+use std::cell::Cell;
+use std::rc::Rc;
 use std::sync::Arc;
 
 /// Runs a mosaic observer. The observer observes blocks from origin and auxiliary. When a new block
@@ -36,90 +41,126 @@ use std::sync::Arc;
 /// * `event_loop` - The reactor's event loop to handle the tasks spawned by this observer.
 /// * `config` - The configuration object of mosaic.
 pub fn run(
-    origin: Arc<Ethereum>,
-    auxiliary: Arc<Ethereum>,
+    origin: Arc<dyn Provider>,
+    auxiliary: Arc<dyn Provider>,
     event_loop: &tokio_core::reactor::Handle,
     config: &Config,
 ) {
-    let cloned_origin = Arc::clone(&origin);
-    let cloned_auxiliary = Arc::clone(&auxiliary);
+    let origin_events = Arc::new(event::origin_event_handler(config));
+    let auxiliary_events = Arc::new(event::auxiliary_event_handler(config));
 
-    let origin_events = event::origin_event_handler(config);
-    let auxiliary_events = event::auxiliary_event_handler(config);
-
-    let origin_stream = origin.stream_blocks(Arc::new(origin_events));
-    let auxiliary_stream = auxiliary.stream_blocks(Arc::new(auxiliary_events));
-
-    let origin_worker = worker(origin_stream, origin_block_function, cloned_origin);
-    let auxiliary_worker = worker(auxiliary_stream, auxiliary_block_function, cloned_auxiliary);
+    let origin_worker = worker(origin, origin_events, origin_block_function);
+    let auxiliary_worker = worker(auxiliary, auxiliary_events, auxiliary_block_function);
 
     event_loop.spawn(origin_worker);
     event_loop.spawn(auxiliary_worker);
 }
 
-/// A worker takes a block stream and a function to apply to each block. The function takes the
-/// block as an argument and returns a result. If it returns an error the error will be logged.
+/// A worker drives a provider's block stream, applying `block_function` to every chain update it
+/// observes.
+///
+/// `stream_blocks` can fail, e.g. because the node restarted and dropped its connection or because
+/// the underlying blocks filter expired. Rather than letting that permanently halt observation,
+/// the worker is supervised: whenever the stream ends or errors out, it reconnects by asking the
+/// provider for a new stream, first backfilling every block mined since the last one the worker
+/// successfully processed so that no block is skipped across the reconnect.
 ///
 /// # Arguments
 ///
-/// * `block_stream` - A stream of block items.
+/// * `block_chain` - The provider to stream blocks from, and to reconnect to on failure.
+/// * `event_handler` - A handler that converts raw logs from the web3 blocks into events. Reused
+///   across reconnects.
 /// * `block_function` - A function that will be called with every block as an argument.
 fn worker<F>(
-    block_stream: impl Stream<Item = Block, Error = Error>,
+    block_chain: Arc<dyn Provider>,
+    event_handler: Arc<EventHandler>,
     block_function: F,
-    block_chain: Arc<Ethereum>,
 ) -> impl Future<Item = (), Error = ()>
 where
-    F: Fn(&Block, &Arc<Ethereum>) -> Result<(), Error>,
+    F: Fn(&ChainUpdate<MosaicEvent>, &Arc<dyn Provider>) -> Result<(), Error> + Clone + 'static,
 {
-    // Using `then` to catch errors. If the errors weren't caught, the stream would terminate after
-    // an error. However, we want to continue polling the node for new blocks, even if there was an
-    // error with a particular block. In the `for_each` block we need to then check for an existing
-    // block as we caught all blocks and errors and mapped both to `Option`al blocks (`None` in the
-    // error case).
-    block_stream
-        .then(|item| match item {
-            Ok(block) => Ok(Some(block)),
-            Err(error) => {
-                error!("Error when streaming blocks: {}", error);
-                Ok(None)
-            }
-        }).for_each(move |block| {
-            let block = match block {
-                Some(block) => block,
-                None => return Ok(()),
+    // The height of the last block that was successfully handed to `block_function`, if any.
+    // Threaded through the reconnect loop as its state, so that a reconnect after block N starts
+    // backfilling from block N + 1 rather than replaying blocks we already processed.
+    future::loop_fn(None, move |last_processed_height: Option<u64>| {
+        let block_chain = Arc::clone(&block_chain);
+        let event_handler = Arc::clone(&event_handler);
+        let block_function = block_function.clone();
+
+        let backfilled: Box<dyn Stream<Item = ChainUpdate<MosaicEvent>, Error = Error>> =
+            match last_processed_height {
+                Some(height) => block_chain.backfill(height + 1, Arc::clone(&event_handler)),
+                None => Box::new(stream::empty()),
             };
+        let live = block_chain.stream_blocks(Arc::clone(&event_handler));
+
+        let last_processed_height = Rc::new(Cell::new(last_processed_height));
+        let last_processed_height_in_worker = Rc::clone(&last_processed_height);
+        let worker_block_chain = Arc::clone(&block_chain);
+
+        backfilled
+            .chain(live)
+            .for_each(move |chain_update| {
+                if let Err(error) = block_function(&chain_update, &worker_block_chain) {
+                    error!("There was an error when processing a block: {}", error);
+                }
+
+                if let ChainUpdate::Applied(block, _) = &chain_update {
+                    last_processed_height_in_worker.set(Some(block.number.low_u64()));
+                }
 
-            // Here we actually call the block function that does the actual work. The rest around
-            // it is more or less boilerplate.
-            if let Err(error) = block_function(&block, &block_chain) {
-                error!("There was an error when processing a block: {}", error);
-            }
+                Ok(())
+            }).then(move |result| {
+                if let Err(error) = result {
+                    error!(
+                        "Block stream terminated with an error, reconnecting: {}",
+                        error
+                    );
+                } else {
+                    warn!("Block stream ended unexpectedly, reconnecting");
+                }
 
-            Ok(())
-        })
+                Ok(Loop::Continue(last_processed_height.get()))
+            })
+    })
 }
 
 /// origin_block_function implements the actions that should be taken for each block that we observe
 /// on origin.
-fn origin_block_function(block: &Block, origin: &Arc<Ethereum>) -> Result<(), Error> {
+fn origin_block_function(
+    chain_update: &ChainUpdate<MosaicEvent>,
+    origin: &Arc<dyn Provider>,
+) -> Result<(), Error> {
     // `info!`s are just used as an example. The actual logic of how to handle each block will be
     // done here. Should spawn new futures to not block if longer computation.
-    info!("Origin Block:     {}", block);
-    info!("Origin Events:    {:?}", block.events);
+    match chain_update {
+        ChainUpdate::Applied(block, events) => {
+            info!("Origin Block:     {}", block);
+            info!("Origin Events:    {:?}", events);
+        }
+        ChainUpdate::Reverted(block, _) => info!("Origin Block retracted by reorg: {}", block),
+    }
 
-    origin.notify_reactors(&block);
+    origin.notify_reactors(&chain_update);
 
     Ok(())
 }
 
 /// origin_block_function implements the actions that should be taken for each block that we observe
 /// on auxiliary.
-fn auxiliary_block_function(block: &Block, auxiliary: &Arc<Ethereum>) -> Result<(), Error> {
+fn auxiliary_block_function(
+    chain_update: &ChainUpdate<MosaicEvent>,
+    auxiliary: &Arc<dyn Provider>,
+) -> Result<(), Error> {
     // `info!`s are just used as an example. The actual logic of how to handle each block will be
     // done here. Should spawn new futures to not block if longer computation.
-    info!("Auxiliary Block:     {}", block);
-    info!("Auxiliary Events:    {:?}", block.events);
-    auxiliary.notify_reactors(&block);
+    match chain_update {
+        ChainUpdate::Applied(block, events) => {
+            info!("Auxiliary Block:     {}", block);
+            info!("Auxiliary Events:    {:?}", events);
+        }
+        ChainUpdate::Reverted(block, _) => info!("Auxiliary Block retracted by reorg: {}", block),
+    }
+    auxiliary.notify_reactors(&chain_update);
     Ok(())
 }